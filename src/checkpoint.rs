@@ -0,0 +1,95 @@
+use genetics::Genome;
+use settings::RenderingSettings;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+// Appended to the output path to name the sidecar (e.g. "out.gif" -> "out.gif.plasma-ckpt").
+const SIDECAR_SUFFIX: &'static str = ".plasma-ckpt";
+
+/// Progress record written next to a GIF so an interrupted render can resume.
+///
+/// The genome hash and settings fingerprint guard against reusing a checkpoint
+/// that belongs to a different render; when either differs from the current job
+/// the stale sidecar is ignored and the render starts from frame 0.
+pub struct Checkpoint {
+    pub genome_hash: u64,
+    pub settings_fingerprint: u64,
+    pub last_frame: usize
+}
+
+impl Checkpoint {
+    /// The sidecar path that pairs with a given GIF output path.
+    pub fn sidecar_path<P: AsRef<Path>>(output_path: P) -> PathBuf {
+        let mut name = output_path.as_ref().as_os_str().to_os_string();
+        name.push(SIDECAR_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    /// Load the checkpoint for `output_path`, if one exists and parses cleanly.
+    pub fn load<P: AsRef<Path>>(output_path: P) -> Option<Checkpoint> {
+        let mut contents = String::new();
+        File::open(Checkpoint::sidecar_path(output_path))
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .ok()?;
+        let mut fields = contents.split_whitespace();
+        let genome_hash = fields.next()?.parse().ok()?;
+        let settings_fingerprint = fields.next()?.parse().ok()?;
+        let last_frame = fields.next()?.parse().ok()?;
+        Some(Checkpoint {
+            genome_hash: genome_hash,
+            settings_fingerprint: settings_fingerprint,
+            last_frame: last_frame
+        })
+    }
+
+    /// Write this checkpoint to the sidecar for `output_path`, overwriting any
+    /// previous record.
+    pub fn save<P: AsRef<Path>>(&self, output_path: P) -> std::io::Result<()> {
+        let line = format!(
+            "{} {} {}\n",
+            self.genome_hash, self.settings_fingerprint, self.last_frame
+        );
+        File::create(Checkpoint::sidecar_path(output_path))
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+    }
+
+    /// Whether this checkpoint describes the render about to be run.
+    pub fn matches(&self, genome: &Genome, settings: &RenderingSettings) -> bool {
+        self.genome_hash == genome_hash(genome)
+            && self.settings_fingerprint == settings_fingerprint(settings)
+    }
+
+    /// Remove the sidecar once a render has finished cleanly.
+    pub fn clear<P: AsRef<Path>>(output_path: P) {
+        let _ = std::fs::remove_file(Checkpoint::sidecar_path(output_path));
+    }
+}
+
+/// Hash a genome so a resumed render can confirm it matches the checkpoint.
+pub fn genome_hash(genome: &Genome) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    genome.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Fingerprint the frame-affecting rendering settings. Frame count, dimensions,
+/// palette and interpolation choices all change the encoded bytes, so a change
+/// in any of them invalidates an existing checkpoint.
+pub fn settings_fingerprint(settings: &RenderingSettings) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // f32s don't implement Hash; hash their bit patterns instead.
+    settings.frames_per_second.to_bits().hash(&mut hasher);
+    settings.loop_duration.to_bits().hash(&mut hasher);
+    settings.dithering.hash(&mut hasher);
+    settings.palette_size.hash(&mut hasher);
+    settings.lookup_mode.hash(&mut hasher);
+    settings.interpolation_space.hash(&mut hasher);
+    settings.supersample.hash(&mut hasher);
+    settings.resample_kernel.hash(&mut hasher);
+    settings.width.hash(&mut hasher);
+    settings.height.hash(&mut hasher);
+    hasher.finish()
+}
@@ -1,9 +1,124 @@
 use fastmath::FastMath;
 use genetics::{Chromosome, Gene};
 use gradient::{Color, ControlPoint, Gradient};
+use settings::RenderingSettings;
 
 const LOOKUP_TABLE_SIZE: usize = 256;
 
+/// How `ColorMapper::convert` turns a plasma value into a palette color.
+///
+/// `Nearest` snaps to the closest table entry (fast, but visibly bands once the
+/// palette is quantized down), while `Linear` blends the two neighbouring
+/// entries so gradients stay smooth regardless of `palette_size`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LookupMode {
+    Nearest,
+    Linear
+}
+
+/// The color space in which `Gradient`/`ControlPoint` blends adjacent control
+/// points while filling the lookup table.
+///
+/// `Srgb` lerps the raw sRGB bytes, which is cheap but drags transitions between
+/// distant hues through muddy, desaturated midpoints. `Lab` lerps in a
+/// perceptually-uniform CIE L*a*b* space so those midpoints stay vivid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InterpolationSpace {
+    Srgb,
+    Lab
+}
+
+// D65 reference white used for the XYZ <-> Lab conversion.
+const LAB_XN: f32 = 0.95047;
+const LAB_YN: f32 = 1.0;
+const LAB_ZN: f32 = 1.08883;
+
+impl Color {
+    /// Convert an sRGB color to CIE L*a*b* (L, a, b).
+    fn to_lab(&self) -> (f32, f32, f32) {
+        let r = srgb_to_linear(self.r);
+        let g = srgb_to_linear(self.g);
+        let b = srgb_to_linear(self.b);
+
+        // Linear sRGB -> XYZ (D65).
+        let x = 0.4124*r + 0.3576*g + 0.1805*b;
+        let y = 0.2126*r + 0.7152*g + 0.0722*b;
+        let z = 0.0193*r + 0.1192*g + 0.9505*b;
+
+        let fx = lab_f(x/LAB_XN);
+        let fy = lab_f(y/LAB_YN);
+        let fz = lab_f(z/LAB_ZN);
+
+        (116.0*fy - 16.0, 500.0*(fx - fy), 200.0*(fy - fz))
+    }
+
+    /// Invert the `to_lab` chain, clamping back into a valid sRGB byte triple.
+    fn from_lab(l: f32, a: f32, b: f32) -> Color {
+        let fy = (l + 16.0)/116.0;
+        let fx = fy + a/500.0;
+        let fz = fy - b/200.0;
+
+        let x = LAB_XN*lab_f_inv(fx);
+        let y = LAB_YN*lab_f_inv(fy);
+        let z = LAB_ZN*lab_f_inv(fz);
+
+        // XYZ -> linear sRGB (D65).
+        let r =  3.2406*x - 1.5372*y - 0.4986*z;
+        let g = -0.9689*x + 1.8758*y + 0.0415*z;
+        let bl =  0.0557*x - 0.2040*y + 1.0570*z;
+
+        Color::new(linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(bl))
+    }
+
+    /// Blend two colors by `position` in [0, 1] through CIE L*a*b* space.
+    pub fn lerp_lab(&self, other: Color, position: f32) -> Color {
+        let (l0, a0, b0) = self.to_lab();
+        let (l1, a1, b1) = other.to_lab();
+        Color::from_lab(
+            l0.lerp(l1, position),
+            a0.lerp(a1, position),
+            b0.lerp(b1, position)
+        )
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = (c as f32)/255.0;
+    if c <= 0.04045 {
+        c/12.92
+    } else {
+        ((c + 0.055)/1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let s = if c <= 0.0031308 {
+        c*12.92
+    } else {
+        1.055*c.powf(1.0/2.4) - 0.055
+    };
+    (s*255.0).round().clamp(0.0, 255.0) as u8
+}
+
+fn lab_f(t: f32) -> f32 {
+    const DELTA: f32 = 6.0/29.0;
+    if t > DELTA*DELTA*DELTA {
+        t.powf(1.0/3.0)
+    } else {
+        t*(29.0/6.0)*(29.0/6.0)/3.0 + 4.0/29.0
+    }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    const DELTA: f32 = 6.0/29.0;
+    if t > DELTA {
+        t*t*t
+    } else {
+        3.0*DELTA*DELTA*(t - 4.0/29.0)
+    }
+}
+
 impl Color {
     fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color {
         let h = hue.wrap();
@@ -51,12 +166,14 @@ impl ControlPoint {
     }
 }
 
+#[derive(Clone)]
 pub struct ColorMapper {
-    lookup_table: [Color; LOOKUP_TABLE_SIZE]
+    lookup_table: [Color; LOOKUP_TABLE_SIZE],
+    lookup_mode: LookupMode
 }
 
 impl ColorMapper {
-    pub fn new(chromosome: &Chromosome) -> ColorMapper {
+    pub fn new(chromosome: &Chromosome, settings: &RenderingSettings) -> ColorMapper {
         let mut lookup_table = [Color {r:0, g:0, b:0}; LOOKUP_TABLE_SIZE];
         let mut control_points = vec![];
         for gene in chromosome.genes.iter() {
@@ -64,7 +181,7 @@ impl ColorMapper {
                 control_points.push(cp);
             }
         }
-        let gradient = Gradient::new(control_points);
+        let gradient = Gradient::new(control_points, settings.interpolation_space);
         let mut iter = gradient.iter();
         let mut subgradient = iter.next().unwrap();
         for i in 0..LOOKUP_TABLE_SIZE {
@@ -76,22 +193,118 @@ impl ColorMapper {
         }
 
         ColorMapper {
-            lookup_table: lookup_table
+            lookup_table: lookup_table,
+            lookup_mode: settings.lookup_mode
         }
     }
 
     pub fn convert(&self, value: f32) -> Color {
-        let index = (value.wrap()*(LOOKUP_TABLE_SIZE as f32)).floor() as usize % LOOKUP_TABLE_SIZE;
-        self.lookup_table[index]
+        let v = value.wrap();
+        match self.lookup_mode {
+            LookupMode::Nearest => {
+                let index = (v*(LOOKUP_TABLE_SIZE as f32)).floor() as usize % LOOKUP_TABLE_SIZE;
+                self.lookup_table[index]
+            },
+            LookupMode::Linear => {
+                // Blend the two neighbouring table entries. The top index wraps
+                // table[SIZE-1] back into table[0] so the cyclic palette has no seam.
+                let ix = v*(LOOKUP_TABLE_SIZE as f32);
+                let base = ix.floor();
+                let i0 = (base as usize) % LOOKUP_TABLE_SIZE;
+                let i1 = (i0 + 1) % LOOKUP_TABLE_SIZE;
+                let frac = ix - base;
+                let c0 = self.lookup_table[i0];
+                let c1 = self.lookup_table[i1];
+                Color::new(
+                    lerp_channel(c0.r, c1.r, frac),
+                    lerp_channel(c0.g, c1.g, frac),
+                    lerp_channel(c0.b, c1.b, frac)
+                )
+            }
+        }
+    }
+
+    /// The color the render loop uses when dithering is off. Goes through
+    /// `convert`, so it honors `lookup_mode` rather than always snapping to the
+    /// nearest table entry.
+    pub fn get_nearest_color(&self, value: f32) -> Color {
+        self.convert(value)
+    }
+
+    /// The color the render loop uses when dithering is on. Nudges `value` by a
+    /// 4x4 ordered (Bayer) offset keyed on the pixel position before handing it
+    /// to `convert`, which breaks up the banding `LookupMode::Nearest` would
+    /// otherwise show without adding noticeable noise to `LookupMode::Linear`.
+    pub fn get_dithered_color(&self, value: f32, x: usize, y: usize) -> Color {
+        const BAYER_4X4: [[i32; 4]; 4] = [
+            [ 0,  8,  2, 10],
+            [12,  4, 14,  6],
+            [ 3, 11,  1,  9],
+            [15,  7, 13,  5]
+        ];
+        let threshold = (BAYER_4X4[y % 4][x % 4] as f32 + 0.5)/16.0 - 0.5;
+        let jitter = threshold/(LOOKUP_TABLE_SIZE as f32);
+        self.convert(value + jitter)
     }
 }
 
+fn lerp_channel(a: u8, b: u8, frac: f32) -> u8 {
+    (a as f32).lerp(b as f32, frac).round() as u8
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{lerp_channel, ColorMapper, LookupMode, LOOKUP_TABLE_SIZE};
     use genetics::Gene;
     use gradient::Color;
     use gradient::ControlPoint;
 
+    // get_nearest_color must dispatch through lookup_mode, not always snap to
+    // the nearest table entry: Linear should differ from Nearest between entries.
+    #[test]
+    fn test_get_nearest_color_honors_lookup_mode() {
+        let mut lookup_table = [Color::new(0, 0, 0); LOOKUP_TABLE_SIZE];
+        lookup_table[0] = Color::new(0, 0, 0);
+        lookup_table[1] = Color::new(100, 0, 0);
+        let value = 0.5/(LOOKUP_TABLE_SIZE as f32); // halfway between entries 0 and 1
+
+        let nearest = ColorMapper { lookup_table, lookup_mode: LookupMode::Nearest };
+        let linear = ColorMapper { lookup_table, lookup_mode: LookupMode::Linear };
+        assert_eq!(nearest.get_nearest_color(value), Color::new(0, 0, 0));
+        assert_eq!(linear.get_nearest_color(value), Color::new(50, 0, 0));
+    }
+
+    // Linear channel blend rounds to the nearest byte and hits both endpoints exactly
+    #[test]
+    fn test_lerp_channel() {
+        assert_eq!(lerp_channel(0, 100, 0.0), 0);
+        assert_eq!(lerp_channel(0, 100, 1.0), 100);
+        assert_eq!(lerp_channel(0, 100, 0.5), 50);
+        assert_eq!(lerp_channel(10, 20, 0.25), 13); // 12.5 rounds to 13
+    }
+
+    // A Lab round-trip should return (close to) the original sRGB color
+    #[test]
+    fn test_lab_roundtrip() {
+        for &c in &[Color::new(0, 0, 0), Color::new(255, 255, 255),
+                    Color::new(255, 0, 0), Color::new(12, 180, 97)] {
+            let (l, a, b) = c.to_lab();
+            let back = Color::from_lab(l, a, b);
+            assert!((back.r as i32 - c.r as i32).abs() <= 1);
+            assert!((back.g as i32 - c.g as i32).abs() <= 1);
+            assert!((back.b as i32 - c.b as i32).abs() <= 1);
+        }
+    }
+
+    // Blending the endpoints in Lab space still hits the endpoints exactly
+    #[test]
+    fn test_lerp_lab_endpoints() {
+        let red = Color::new(255, 0, 0);
+        let blue = Color::new(0, 0, 255);
+        assert_eq!(red.lerp_lab(blue, 0.0), red);
+        assert_eq!(red.lerp_lab(blue, 1.0), blue);
+    }
+
     #[test]
     fn test_color_from_hsv() {
         /*
@@ -0,0 +1,153 @@
+//! GIF output.
+//!
+//! Frames are rendered one at a time and collected into an animated GIF. A
+//! render can be bounded by a wall-clock `time_limit`: when the budget elapses
+//! mid-sequence we finalize a valid (possibly shorter) GIF and leave a sidecar
+//! checkpoint so a later invocation picks up where this one stopped. The
+//! already-rendered frames are cached next to the output so resuming skips the
+//! expensive render work rather than redoing it.
+
+use checkpoint::{self, Checkpoint};
+use gif::{Encoder, Frame, Repeat, SetParameter};
+use num_cpus;
+use renderer::{Image, PlasmaRenderer};
+use settings::{OutputMode, PlasmaSettings};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+// Appended to the output path to hold the raw pixels of completed frames.
+const FRAME_CACHE_SUFFIX: &'static str = ".plasma-frames";
+
+pub fn generate_gif_bytes(settings: PlasmaSettings) -> Vec<u8> {
+    let genome = settings.genetics.genome.clone();
+    let rendering = &settings.rendering;
+    let width = rendering.width;
+    let height = rendering.height;
+    let frame_size = width*height*3;
+    let num_frames = ((rendering.loop_duration*rendering.frames_per_second).round() as usize).max(1);
+
+    // Checkpointing is only possible when we have an output path to anchor the
+    // sidecar and frame cache to.
+    let output_path = match settings.output.mode {
+        OutputMode::File { ref path } => Some(PathBuf::from(path)),
+        _ => None
+    };
+    let cache_path = output_path.as_ref().map(frame_cache_path);
+
+    // Resume from a matching checkpoint, reusing any frames already rendered.
+    let mut frames: Vec<Vec<u8>> = vec![];
+    if let (Some(path), Some(cache)) = (output_path.as_ref(), cache_path.as_ref()) {
+        if let Some(cp) = Checkpoint::load(path) {
+            if cp.matches(&genome, rendering) {
+                frames = read_frame_cache(cache, frame_size);
+                frames.truncate(cp.last_frame + 1);
+                // Keep the on-disk cache in lockstep with what we just kept in
+                // memory: a crash between append_frame_cache and checkpoint.save
+                // can leave one more frame on disk than the checkpoint recorded,
+                // and blind-appending past that would misalign every frame after it.
+                truncate_frame_cache(cache, frame_size, frames.len());
+            }
+        }
+        if frames.is_empty() {
+            let _ = fs::remove_file(cache);
+            Checkpoint::clear(path);
+        }
+    }
+
+    let mut renderer = PlasmaRenderer::new(&genome, rendering);
+    // Saturate all cores within each frame so multi-frame GIFs aren't limited to
+    // one core per frame.
+    let chunk_count = num_cpus::get();
+    let start_frame = frames.len();
+    let start_time = Instant::now();
+    for i in start_frame..num_frames {
+        // Stop cleanly once the time budget is exhausted, leaving a checkpoint.
+        if let Some(limit) = rendering.time_limit {
+            if elapsed_secs(&start_time) >= limit {
+                break;
+            }
+        }
+        let mut image = Image::new(width, height);
+        renderer.render_parallel(&mut image, (i as f32)/(num_frames as f32), chunk_count);
+        if let Some(cache) = cache_path.as_ref() {
+            append_frame_cache(cache, &image.pixel_data);
+        }
+        frames.push(image.pixel_data);
+        if let Some(path) = output_path.as_ref() {
+            let checkpoint = Checkpoint {
+                genome_hash: checkpoint::genome_hash(&genome),
+                settings_fingerprint: checkpoint::settings_fingerprint(rendering),
+                last_frame: i
+            };
+            let _ = checkpoint.save(path);
+        }
+    }
+
+    // Once every frame is rendered the sidecar and cache are no longer needed.
+    if frames.len() >= num_frames {
+        if let (Some(path), Some(cache)) = (output_path.as_ref(), cache_path.as_ref()) {
+            Checkpoint::clear(path);
+            let _ = fs::remove_file(cache);
+        }
+    }
+
+    encode_gif(&frames, width, height, rendering.frames_per_second)
+}
+
+fn encode_gif(frames: &[Vec<u8>], width: usize, height: usize, fps: f32) -> Vec<u8> {
+    let delay = (100.0/fps).round() as u16;
+    let mut bytes = vec![];
+    {
+        let mut encoder = Encoder::new(&mut bytes, width as u16, height as u16, &[]).unwrap();
+        encoder.set(Repeat::Infinite).unwrap();
+        for pixels in frames {
+            let mut frame = Frame::from_rgb(width as u16, height as u16, pixels);
+            frame.delay = delay;
+            encoder.write_frame(&frame).unwrap();
+        }
+    }
+    bytes
+}
+
+fn frame_cache_path(output_path: &PathBuf) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(FRAME_CACHE_SUFFIX);
+    PathBuf::from(name)
+}
+
+fn read_frame_cache(path: &PathBuf, frame_size: usize) -> Vec<Vec<u8>> {
+    let mut contents = vec![];
+    if File::open(path).and_then(|mut f| f.read_to_end(&mut contents)).is_err() {
+        return vec![];
+    }
+    // Only whole frames are usable; a torn final write is discarded.
+    contents.chunks(frame_size)
+        .filter(|chunk| chunk.len() == frame_size)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
+fn append_frame_cache(path: &PathBuf, pixels: &[u8]) {
+    let _ = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(pixels));
+}
+
+// Trims the cache file back to exactly `frame_count` whole frames, discarding
+// any stale frame left behind by a crash between append_frame_cache and
+// checkpoint.save so later appends stay aligned with frame index.
+fn truncate_frame_cache(path: &PathBuf, frame_size: usize, frame_count: usize) {
+    let _ = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_len((frame_size*frame_count) as u64));
+}
+
+fn elapsed_secs(start: &Instant) -> f32 {
+    let elapsed = start.elapsed();
+    (elapsed.as_secs() as f32) + (elapsed.subsec_nanos() as f32)/1_000_000_000.0
+}
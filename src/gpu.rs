@@ -0,0 +1,244 @@
+//! Compute-shader plasma backend.
+//!
+//! This mirrors `PlasmaRenderer::render`: it scales screen coordinates so the
+//! smaller dimension ranges from -1.0 to 1.0, evaluates the formula as a sum of
+//! sines, wraps the result into [0.0, 1.0) and indexes the 256-entry color
+//! table. The formula parameters and color table are uploaded as storage
+//! buffers; the shader writes an interleaved RGB image that is read back into an
+//! `Image`. `GpuRenderer::new` returns `None` when no adapter is available so
+//! callers can fall back to the CPU path.
+
+use color::Color;
+use formulas::PlasmaFormulas;
+use futures::executor::block_on;
+use renderer::Image;
+use std::mem;
+
+// Must match the `workgroup_size` declared in the shader below.
+const WORKGROUP_SIZE: u32 = 8;
+
+const SHADER_SOURCE: &'static str = r#"
+struct Term {
+    amp: f32,
+    fx: f32,
+    fy: f32,
+    phase: f32,
+};
+
+struct Uniforms {
+    width: u32,
+    height: u32,
+    term_count: u32,
+    scale_mul: f32,
+    scale_x_offset: f32,
+    scale_y_offset: f32,
+    _pad0: u32,
+    _pad1: u32,
+};
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var<storage, read> terms: array<Term>;
+@group(0) @binding(2) var<storage, read> palette: array<u32>; // packed 0x00RRGGBB
+@group(0) @binding(3) var<storage, read_write> output: array<u32>;
+
+fn wrap(v: f32) -> f32 {
+    return v - floor(v);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= uniforms.width || gid.y >= uniforms.height) {
+        return;
+    }
+    let px = uniforms.scale_mul*f32(gid.x) + uniforms.scale_x_offset;
+    let py = uniforms.scale_mul*f32(gid.y) + uniforms.scale_y_offset;
+
+    var value = 0.0;
+    for (var i = 0u; i < uniforms.term_count; i = i + 1u) {
+        let t = terms[i];
+        value = value + t.amp*sin(t.fx*px + t.fy*py + t.phase);
+    }
+
+    let index = u32(floor(wrap(value)*256.0)) % 256u;
+    output[gid.x + gid.y*uniforms.width] = palette[index];
+}
+"#;
+
+// WGSL lays out a uniform-address-space struct with its size rounded up to a
+// multiple of 16 bytes, so this 24-byte payload needs two trailing u32s of
+// padding to avoid tripping wgpu's min-binding-size validation on upload.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Uniforms {
+    width: u32,
+    height: u32,
+    term_count: u32,
+    scale_mul: f32,
+    scale_x_offset: f32,
+    scale_y_offset: f32,
+    _pad0: u32,
+    _pad1: u32
+}
+
+pub struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline
+}
+
+impl GpuRenderer {
+    /// Acquire a compute device, or `None` when no adapter can be found.
+    pub fn new() -> Option<GpuRenderer> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false
+        }))?;
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor::default(),
+            None
+        )).ok()?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("plasma"),
+            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into())
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("plasma"),
+            layout: None,
+            module: &shader,
+            entry_point: "main"
+        });
+
+        Some(GpuRenderer {
+            device: device,
+            queue: queue,
+            pipeline: pipeline
+        })
+    }
+
+    /// Render a single frame, mirroring the CPU coordinate scaling and palette
+    /// indexing exactly so the two backends agree.
+    pub fn render(&self, formulas: &PlasmaFormulas, palette: &[Color], width: usize,
+                  height: usize, time: f32) -> Image {
+        use wgpu::util::DeviceExt;
+
+        let scale_mul = 2.0/((width as f32).min(height as f32));
+        let uniforms = Uniforms {
+            width: width as u32,
+            height: height as u32,
+            term_count: 0, // set below from the serialized terms
+            scale_mul: scale_mul,
+            scale_x_offset: -(width as f32)/2.0*scale_mul,
+            scale_y_offset: -(height as f32)/2.0*scale_mul,
+            _pad0: 0,
+            _pad1: 0
+        };
+
+        // Serialize the formula into shader-visible terms (amp, fx, fy, phase),
+        // folding the frame time into the phases up front.
+        let terms = formulas.shader_terms(time.wrap());
+        let mut uniforms = uniforms;
+        uniforms.term_count = terms.len() as u32;
+
+        // Pack the palette as 0x00RRGGBB so the shader can write it directly.
+        let packed_palette: Vec<u32> = palette.iter()
+            .map(|c| ((c.r as u32) << 16) | ((c.g as u32) << 8) | (c.b as u32))
+            .collect();
+
+        let uniform_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("uniforms"),
+            contents: as_bytes(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let term_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("terms"),
+            contents: as_bytes(&terms),
+            usage: wgpu::BufferUsages::STORAGE
+        });
+        let palette_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("palette"),
+            contents: as_bytes(&packed_palette),
+            usage: wgpu::BufferUsages::STORAGE
+        });
+
+        let pixel_count = width*height;
+        let output_size = (pixel_count*mem::size_of::<u32>()) as u64;
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("output"),
+            size: output_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false
+        });
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: output_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("plasma"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: term_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: palette_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: output_buffer.as_entire_binding() }
+            ]
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("plasma")
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("plasma")
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let groups_x = (width as u32 + WORKGROUP_SIZE - 1)/WORKGROUP_SIZE;
+            let groups_y = (height as u32 + WORKGROUP_SIZE - 1)/WORKGROUP_SIZE;
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&output_buffer, 0, &readback_buffer, 0, output_size);
+        self.queue.submit(Some(encoder.finish()));
+
+        // Map the readback buffer and unpack into interleaved RGB.
+        let slice = readback_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let packed: Vec<u32> = {
+            let data = slice.get_mapped_range();
+            bytemuck_cast(&data)
+        };
+        readback_buffer.unmap();
+
+        let mut image = Image::new(width, height);
+        for (i, packed) in packed.iter().enumerate().take(pixel_count) {
+            let offset = i*3;
+            image.pixel_data[offset] = ((packed >> 16) & 0xff) as u8;
+            image.pixel_data[offset + 1] = ((packed >> 8) & 0xff) as u8;
+            image.pixel_data[offset + 2] = (packed & 0xff) as u8;
+        }
+        image
+    }
+}
+
+// The term/uniform/palette payloads are plain-old-data; reinterpret them as the
+// byte slices wgpu's buffer uploads expect.
+fn as_bytes<T: Copy>(slice: &[T]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(
+            slice.as_ptr() as *const u8,
+            slice.len()*mem::size_of::<T>()
+        )
+    }
+}
+
+fn bytemuck_cast(bytes: &[u8]) -> Vec<u32> {
+    bytes.chunks_exact(4)
+        .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
@@ -1,20 +1,30 @@
 extern crate cgmath;
 extern crate getopts;
 extern crate gif;
+extern crate num_cpus;
 extern crate ordered_float;
 extern crate sdl2;
+#[cfg(feature = "gpu")]
+extern crate wgpu;
 
 mod asyncrenderer;
+mod checkpoint;
 mod color;
 mod fastmath;
 mod file;
 mod formulas;
 mod genetics;
+#[cfg(feature = "gpu")]
+mod gpu;
 mod interactive;
 mod renderer;
+mod scale;
 mod settings;
+#[cfg(test)]
+mod testutil;
 
-use color::colormapper::{NUM_COLOR_GENES, CONTROL_POINT_GENE_SIZE};
+use color::colormapper::{NUM_COLOR_GENES, CONTROL_POINT_GENE_SIZE, InterpolationSpace, LookupMode};
+use scale::Kernel;
 use formulas::{NUM_FORMULA_GENES, FORMULA_GENE_SIZE};
 use getopts::{Matches, Options};
 use genetics::{Chromosome, Genome, Population};
@@ -93,6 +103,10 @@ fn create_options() -> Options {
     opts.optopt("p", "palette", "Render using a color palette of a given size", "N");
     opts.optopt("f", "fps", "Frames per second", "N");
     opts.optopt("l", "loop-duration", "Seconds until the animation loops", "N");
+    opts.optopt("", "time-limit", "Render for at most SECONDS, resuming from a checkpoint", "SECONDS");
+    opts.optopt("", "supersample", "Render at N times the target size and downscale for anti-aliasing", "N");
+    opts.optopt("", "kernel", "Resampling kernel for supersampling: box, triangle, lanczos2, lanczos3", "NAME");
+    opts.optopt("", "lookup", "Palette lookup mode: nearest, linear (nearest is required to use the GPU backend)", "MODE");
     opts.optopt("i", "input", "Read genomes from file, one genome per line", "FILE");
     opts.optopt("o", "output", "Output to a file (GIF) instead of to a window", "FILE");
     opts.optopt("w", "width", "Width, in pixels", "X");
@@ -163,6 +177,11 @@ fn build_plasma_settings(matches: Matches) -> Result<PlasmaSettings, String> {
             frames_per_second: 16.0,
             loop_duration: 60.0,
             palette_size: None,
+            lookup_mode: LookupMode::Linear,
+            interpolation_space: InterpolationSpace::Lab,
+            time_limit: None,
+            supersample: 1,
+            resample_kernel: Kernel::Triangle,
             width: 640,
             height: 480
         },
@@ -171,6 +190,11 @@ fn build_plasma_settings(matches: Matches) -> Result<PlasmaSettings, String> {
             frames_per_second: 10.0,
             loop_duration: 60.0,
             palette_size: Some(64),
+            lookup_mode: LookupMode::Nearest,
+            interpolation_space: InterpolationSpace::Lab,
+            time_limit: None,
+            supersample: 2,
+            resample_kernel: Kernel::Triangle,
             width: 320,
             height: 240
         }
@@ -193,6 +217,34 @@ fn build_plasma_settings(matches: Matches) -> Result<PlasmaSettings, String> {
             _ => return Err(format!("Not a positive number: {}", loop_duration_str))
         };
     }
+    if let Some(supersample_str) = matches.opt_str("supersample") {
+        rendering_settings.supersample = match supersample_str.parse() {
+            Ok(n) if n >= 1 => n,
+            _ => return Err(format!("Not an integer >= 1: {}", supersample_str))
+        };
+    }
+    if let Some(kernel_str) = matches.opt_str("kernel") {
+        rendering_settings.resample_kernel = match kernel_str.as_ref() {
+            "box" => Kernel::Box,
+            "triangle" => Kernel::Triangle,
+            "lanczos2" => Kernel::Lanczos2,
+            "lanczos3" => Kernel::Lanczos3,
+            _ => return Err(format!("Unknown kernel: {}", kernel_str))
+        };
+    }
+    if let Some(lookup_str) = matches.opt_str("lookup") {
+        rendering_settings.lookup_mode = match lookup_str.as_ref() {
+            "nearest" => LookupMode::Nearest,
+            "linear" => LookupMode::Linear,
+            _ => return Err(format!("Unknown lookup mode: {}", lookup_str))
+        };
+    }
+    if let Some(time_limit_str) = matches.opt_str("time-limit") {
+        rendering_settings.time_limit = match time_limit_str.parse() {
+            Ok(n) if n > 0.0 => Some(n),
+            _ => return Err(format!("Not a positive number: {}", time_limit_str))
+        };
+    }
     if let Some(palette_size_str) = matches.opt_str("p") {
         // TODO: Add support for 256 colors
         rendering_settings.palette_size = match palette_size_str.parse() {
@@ -0,0 +1,176 @@
+//! Separable image resampling used to downscale a supersampled render back to
+//! the target size. Each output pixel's contributing input samples and their
+//! weights are precomputed once per axis (the scale factor is constant across a
+//! frame), then applied in a horizontal pass followed by a vertical pass,
+//! independently per RGB channel.
+
+use renderer::Image;
+use std::f32::consts::PI;
+
+/// Resampling kernel used when downscaling a supersampled image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kernel {
+    Box,
+    Triangle,
+    Lanczos2,
+    Lanczos3
+}
+
+impl Kernel {
+    // Radius of the kernel's support, in output-sample units.
+    fn support(&self) -> f32 {
+        match *self {
+            Kernel::Box => 0.5,
+            Kernel::Triangle => 1.0,
+            Kernel::Lanczos2 => 2.0,
+            Kernel::Lanczos3 => 3.0
+        }
+    }
+
+    fn eval(&self, x: f32) -> f32 {
+        match *self {
+            Kernel::Box => if x.abs() < 0.5 { 1.0 } else { 0.0 },
+            Kernel::Triangle => (1.0 - x.abs()).max(0.0),
+            Kernel::Lanczos2 => lanczos(x, 2.0),
+            Kernel::Lanczos3 => lanczos(x, 3.0)
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let px = PI*x;
+        px.sin()/px
+    }
+}
+
+fn lanczos(x: f32, a: f32) -> f32 {
+    if x.abs() < a {
+        sinc(x)*sinc(x/a)
+    } else {
+        0.0
+    }
+}
+
+// Per-output-sample weight table for one axis: the first contributing input
+// index and the normalized weights for the run of samples starting there.
+struct AxisWeights {
+    start: isize,
+    weights: Vec<f32>
+}
+
+fn axis_weights(src_len: usize, dst_len: usize, kernel: Kernel) -> Vec<AxisWeights> {
+    let ratio = (src_len as f32)/(dst_len as f32);
+    // Widen the kernel when downsampling so it acts as a low-pass filter.
+    let filter_scale = ratio.max(1.0);
+    let support = kernel.support()*filter_scale;
+    let mut table = Vec::with_capacity(dst_len);
+    for i in 0..dst_len {
+        let center = ((i as f32) + 0.5)*ratio - 0.5;
+        let left = (center - support).ceil() as isize;
+        let right = (center + support).floor() as isize;
+        let mut weights = Vec::with_capacity((right - left + 1).max(0) as usize);
+        let mut sum = 0.0;
+        for s in left..=right {
+            let w = kernel.eval(((s as f32) - center)/filter_scale);
+            weights.push(w);
+            sum += w;
+        }
+        if sum != 0.0 {
+            for w in weights.iter_mut() {
+                *w /= sum;
+            }
+        }
+        table.push(AxisWeights { start: left, weights: weights });
+    }
+    table
+}
+
+// Clamp a (possibly out-of-range) sample index to the valid edge.
+fn clamp_index(i: isize, len: usize) -> usize {
+    if i < 0 {
+        0
+    } else if i as usize >= len {
+        len - 1
+    } else {
+        i as usize
+    }
+}
+
+/// Downscale `src` to `dst_width` by `dst_height` using the given kernel.
+pub fn downscale(src: &Image, dst_width: usize, dst_height: usize, kernel: Kernel) -> Image {
+    let x_weights = axis_weights(src.width, dst_width, kernel);
+    let y_weights = axis_weights(src.height, dst_height, kernel);
+
+    // Horizontal pass: src.width -> dst_width, keeping src.height rows.
+    let mut horizontal = vec![0.0f32; dst_width*src.height*3];
+    for y in 0..src.height {
+        let row = y*src.width*3;
+        for (x, w) in x_weights.iter().enumerate() {
+            let mut acc = [0.0f32; 3];
+            for (k, weight) in w.weights.iter().enumerate() {
+                let sx = clamp_index(w.start + k as isize, src.width);
+                let offset = row + sx*3;
+                acc[0] += (src.pixel_data[offset] as f32)*weight;
+                acc[1] += (src.pixel_data[offset + 1] as f32)*weight;
+                acc[2] += (src.pixel_data[offset + 2] as f32)*weight;
+            }
+            let out = (y*dst_width + x)*3;
+            horizontal[out] = acc[0];
+            horizontal[out + 1] = acc[1];
+            horizontal[out + 2] = acc[2];
+        }
+    }
+
+    // Vertical pass: src.height -> dst_height.
+    let mut dst = Image::new(dst_width, dst_height);
+    for (y, w) in y_weights.iter().enumerate() {
+        for x in 0..dst_width {
+            let mut acc = [0.0f32; 3];
+            for (k, weight) in w.weights.iter().enumerate() {
+                let sy = clamp_index(w.start + k as isize, src.height);
+                let offset = (sy*dst_width + x)*3;
+                acc[0] += horizontal[offset]*weight;
+                acc[1] += horizontal[offset + 1]*weight;
+                acc[2] += horizontal[offset + 2]*weight;
+            }
+            let out = (y*dst_width + x)*3;
+            dst.pixel_data[out] = acc[0].round().max(0.0).min(255.0) as u8;
+            dst.pixel_data[out + 1] = acc[1].round().max(0.0).min(255.0) as u8;
+            dst.pixel_data[out + 2] = acc[2].round().max(0.0).min(255.0) as u8;
+        }
+    }
+    dst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{downscale, Kernel};
+    use renderer::Image;
+
+    // Downscaling a solid-color image leaves every pixel that color, for every kernel.
+    #[test]
+    fn test_downscale_solid() {
+        let mut src = Image::new(8, 8);
+        for p in src.pixel_data.iter_mut() {
+            *p = 123;
+        }
+        for &kernel in &[Kernel::Box, Kernel::Triangle, Kernel::Lanczos2, Kernel::Lanczos3] {
+            let dst = downscale(&src, 4, 4, kernel);
+            assert_eq!(dst.width, 4);
+            assert_eq!(dst.height, 4);
+            assert!(dst.pixel_data.iter().all(|&p| p == 123));
+        }
+    }
+
+    // A 2x box downscale averages the two source pixels.
+    #[test]
+    fn test_downscale_box_average() {
+        let mut src = Image::new(2, 1);
+        src.pixel_data = vec![0, 0, 0, 100, 100, 100];
+        let dst = downscale(&src, 1, 1, Kernel::Box);
+        assert_eq!(dst.pixel_data, vec![50, 50, 50]);
+    }
+}
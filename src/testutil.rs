@@ -0,0 +1,31 @@
+//! Shared fixtures for the renderer/asyncrenderer test modules, so a new
+//! `RenderingSettings` field only has to be added in one place.
+
+use color::colormapper::{CONTROL_POINT_GENE_SIZE, InterpolationSpace, LookupMode, NUM_COLOR_GENES};
+use formulas::{FORMULA_GENE_SIZE, NUM_FORMULA_GENES};
+use genetics::{Chromosome, Genome};
+use scale::Kernel;
+use settings::RenderingSettings;
+
+pub fn dummy_settings(width: usize, height: usize) -> RenderingSettings {
+    RenderingSettings {
+        dithering: false,
+        frames_per_second: 16.0,
+        loop_duration: 60.0,
+        palette_size: None,
+        lookup_mode: LookupMode::Linear,
+        interpolation_space: InterpolationSpace::Lab,
+        time_limit: None,
+        supersample: 1,
+        resample_kernel: Kernel::Triangle,
+        width: width,
+        height: height
+    }
+}
+
+pub fn rand_genome() -> Genome {
+    Genome {
+        pattern: Chromosome::rand(NUM_FORMULA_GENES, FORMULA_GENE_SIZE),
+        color: Chromosome::rand(NUM_COLOR_GENES, CONTROL_POINT_GENE_SIZE)
+    }
+}